@@ -1,10 +1,26 @@
 use std::error::Error;
-use std::path::Path;
-use std::time::Duration;
+use std::fs::File as StdFile;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
 use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+// Characters that must be percent-encoded when building an href for a single path
+// segment (filesystem entry name) in a directory listing.
+const PATH_SEGMENT: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'.')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'~');
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -12,252 +28,786 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let listener = TcpListener::bind("127.0.0.1:8080").await?;
     println!("Server listening on port 8080");
 
+    // HTTPS is opt-in: only set up a TLS listener when cert/key paths are configured.
+    if let Some(acceptor) = load_tls_acceptor()? {
+        let tls_listener = TcpListener::bind("127.0.0.1:8443").await?;
+        println!("Server listening on port 8443 (TLS)");
+        tokio::spawn(accept_tls(tls_listener, acceptor));
+    }
+
     loop {
         // The .await make this non-blocking
         let (socket, addr) = listener.accept().await?;
-        println!("Accepted connection from: {}", addr);
 
         // Spawn a new task for each connection
         tokio::spawn(async move {
-            // Add a 30 seconds timeout for handling each connection
-            match timeout(Duration::from_secs(30), handle_connection(socket)).await {
-                Ok(result) => {
-                    // Process the connection
-                    if let Err(e) = result {
-                        eprintln!("Error handling connection: {}", e);
+            if let Err(e) = handle_connection(socket, addr).await {
+                eprintln!("Error handling connection from {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+async fn accept_tls(listener: TcpListener, acceptor: TlsAcceptor) {
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("Error accepting TLS connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+
+        tokio::spawn(async move {
+            match acceptor.accept(socket).await {
+                Ok(tls_stream) => {
+                    if let Err(e) = handle_connection(tls_stream, addr).await {
+                        eprintln!("Error handling TLS connection from {}: {}", addr, e);
                     }
                 }
-                Err(_) => {
-                    eprintln!("Connection handling time out");
-                }
+                Err(e) => eprintln!("TLS handshake with {} failed: {}", addr, e),
             }
         });
     }
 }
 
-async fn handle_connection(mut socket: TcpStream) -> Result<(), Box<dyn Error>> {
-    // Create a buffer to store the request
-    let mut buffer = vec![0u8; 8192]; // 8KB buffer
+// Build a `TlsAcceptor` from a cert chain and private key when both `TLS_CERT_PATH`
+// and `TLS_KEY_PATH` are set; returns `None` to stay plaintext-only otherwise.
+fn load_tls_acceptor() -> Result<Option<TlsAcceptor>, Box<dyn Error>> {
+    let cert_path = std::env::var("TLS_CERT_PATH");
+    let key_path = std::env::var("TLS_KEY_PATH");
+    let (cert_path, key_path) = match (cert_path, key_path) {
+        (Ok(cert_path), Ok(key_path)) => (cert_path, key_path),
+        (Ok(_), Err(_)) => {
+            eprintln!("TLS_CERT_PATH is set but TLS_KEY_PATH is not; staying plaintext-only");
+            return Ok(None);
+        }
+        (Err(_), Ok(_)) => {
+            eprintln!("TLS_KEY_PATH is set but TLS_CERT_PATH is not; staying plaintext-only");
+            return Ok(None);
+        }
+        (Err(_), Err(_)) => return Ok(None),
+    };
+
+    // rustls needs a process-level crypto provider installed before building a config.
+    if rustls::crypto::CryptoProvider::get_default().is_none() {
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .map_err(|_| "failed to install rustls crypto provider")?;
+    }
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(StdFile::open(&cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(StdFile::open(&key_path)?))?
+        .ok_or("no private key found in TLS_KEY_PATH")?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(Some(TlsAcceptor::from(Arc::new(config))))
+}
+
+// A parsed request, stripped of its borrow on the read buffer so it can outlive
+// the buffer being drained/refilled across keep-alive iterations.
+struct ParsedRequest {
+    method: String,
+    path: String,
+    is_http_1_0: bool,
+    range_header: Option<String>,
+    connection_header: Option<String>,
+    consumed: usize,
+}
+
+// Try to parse a single request out of `buffer`. Returns `Ok(None)` when more bytes
+// are needed, `Ok(Some(..))` on a complete request, `Err(())` on a malformed one.
+fn try_parse_request(buffer: &[u8]) -> Result<Option<ParsedRequest>, ()> {
     let mut headers = [httparse::EMPTY_HEADER; 64];
     let mut request = httparse::Request::new(&mut headers);
 
-    // Read bytes from the socket
-    let n = socket.read(&mut buffer).await?;
-    if n == 0 {
-        return Ok(());
+    match request.parse(buffer).map_err(|_| ())? {
+        httparse::Status::Partial => Ok(None),
+        httparse::Status::Complete(consumed) => {
+            let method = request.method.unwrap_or("").to_string();
+            let path = request.path.unwrap_or("").to_string();
+            let is_http_1_0 = request.version == Some(0);
+            let range_header = request
+                .headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case("range"))
+                .map(|h| String::from_utf8_lossy(h.value).into_owned());
+            let connection_header = request
+                .headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case("connection"))
+                .map(|h| String::from_utf8_lossy(h.value).into_owned());
+
+            Ok(Some(ParsedRequest {
+                method,
+                path,
+                is_http_1_0,
+                range_header,
+                connection_header,
+                consumed,
+            }))
+        }
+    }
+}
+
+// Whether the connection should stay open for another request, per the
+// `Connection` header and the HTTP/1.0 vs HTTP/1.1 default.
+fn should_keep_alive(request: &ParsedRequest) -> bool {
+    match request.connection_header.as_deref() {
+        Some(value) if value.eq_ignore_ascii_case("close") => false,
+        Some(value) if value.eq_ignore_ascii_case("keep-alive") => true,
+        _ => !request.is_http_1_0,
+    }
+}
+
+fn connection_header(keep_alive: bool) -> &'static str {
+    if keep_alive {
+        "keep-alive"
+    } else {
+        "close"
     }
+}
 
-    // Parse the request
-    match request.parse(&buffer[..n]) {
-        Ok(httparse::Status::Complete(_size)) => {
-            // Successfully parsed the request
-            let method = request.method.unwrap_or("");
-            let path = request.path.unwrap_or("");
+// Pulls the numeric status out of a status line like "HTTP/1.1 404 NOT FOUND".
+fn status_code_from_line(status_line: &str) -> u16 {
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0)
+}
 
-            println!("Received {} request for {}", method, path);
+// Owns a connection's socket and accumulates per-request state (status code, bytes
+// sent, elapsed time) so every request can end with one structured access-log line.
+// Generic over the stream type so the same request-handling code path serves both
+// plaintext `TcpStream`s and TLS-wrapped ones.
+struct RequestHandle<S> {
+    socket: S,
+    peer_addr: SocketAddr,
+    method: String,
+    path: String,
+    status_code: u16,
+    bytes_sent: u64,
+    started_at: Instant,
+}
 
-            match method {
-                "GET" => handle_get_request(socket, path).await?,
-                _ => {
-                    // Respond with 405 Method Not Allowed
-                    let response = "HTTP/1.1 405 Method Not Allowed\r\n\r\n";
-                    socket.write_all(response.as_bytes()).await?;
-                }
+impl<S: AsyncRead + AsyncWrite + Unpin> RequestHandle<S> {
+    fn new(socket: S, peer_addr: SocketAddr) -> Self {
+        Self {
+            socket,
+            peer_addr,
+            method: String::new(),
+            path: String::new(),
+            status_code: 0,
+            bytes_sent: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    // Reset the accumulated state for the next request on this connection.
+    fn begin_request(&mut self, method: String, path: String) {
+        self.method = method;
+        self.path = path;
+        self.status_code = 0;
+        self.bytes_sent = 0;
+        self.started_at = Instant::now();
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.socket.write_all(data).await?;
+        self.bytes_sent += data.len() as u64;
+        Ok(())
+    }
+
+    // Common-log-format-style access line, emitted once per request.
+    fn log_access(&self) {
+        println!(
+            "{} \"{} {}\" {} {} {:.3}s",
+            self.peer_addr,
+            self.method,
+            self.path,
+            self.status_code,
+            self.bytes_sent,
+            self.started_at.elapsed().as_secs_f64()
+        );
+    }
+
+    async fn handle_get_request(
+        &mut self,
+        path: &str,
+        range_header: Option<&str>,
+        keep_alive: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        // Create a simple router from different paths
+        match path {
+            "/" => {
+                self.serve_static_html(
+                    "<html><body>
+                        <h1>Welcome to Tokio Async Server</h1>
+                        <p>This is a simple async HTTP server built with Tokio.</p>
+                        <ul>
+                            <li><a href='/'>Home</a></li>
+                            <li><a href='/about'>About</a></li>
+                            <li><a href='/files/index.html'>Static File Example</a></li>
+                            <li><a href='/files/'>Files Directory</a></li>
+                        </ul>
+                    </body></html>",
+                    "HTTP/1.1 200 OK",
+                    keep_alive,
+                ).await
+            }
+            "/about" => {
+                self.serve_static_html(
+                    "<html><body>
+                        <h1>About This Server</h1>
+                        <p>This is a demonstration of asynchronous programming in Rust using Tokio.</p>
+                        <p><a href='/'>Back to home</a></p>
+                    </body></html>"
+                    , "HTTP/1.1 200 OK",
+                    keep_alive,
+                ).await
+            }
+            _ if path.starts_with("/files/") => {
+                // Handle file requests
+                self.serve_file(path, range_header, keep_alive).await
+            }
+            _ => {
+                self.serve_static_html(
+                    "<html><body>
+                        <h1>404: Page not found</h1>
+                        <p>The requested resource could not be found.</p>
+                        <p><a href='/'>Back to home</a></p>
+                    </body></html>",
+                    "HTTP/1.1 404 NOT FOUND",
+                    keep_alive,
+                ).await
             }
         }
-        Ok(httparse::Status::Partial) => {
-            // Incomplete request
-            let response =
-                "HTTP/1.1 400 Bad Request\r\nContent-Length: 26\r\n\r\nIncomplete request received";
-            socket.write_all(response.as_bytes()).await?;
+    }
+
+    // Helper to serve static HTML content
+    async fn serve_static_html(&mut self, content: &str, status: &str, keep_alive: bool) -> Result<(), Box<dyn Error>> {
+        self.status_code = status_code_from_line(status);
+        let content_type = "text/html".to_string();
+
+        // Construct the full response
+        let response = format!(
+            "{}\r\nContent-Length: {}\r\nContent-Type: {}\r\nConnection: {}\r\n\r\n{}",
+            status,
+            content.len(),
+            content_type,
+            connection_header(keep_alive),
+            content
+        );
+        self.write_all(response.as_bytes()).await
+    }
+
+    // Helper to serve files
+    async fn serve_file(&mut self, path: &str, range_header: Option<&str>, keep_alive: bool) -> Result<(), Box<dyn Error>> {
+        // Extract and percent-decode the file path from the URL so names with spaces
+        // or unicode (e.g. `My%20File.txt`) resolve correctly.
+        let requested = path.trim_start_matches("/files/");
+        let decoded = match percent_encoding::percent_decode_str(requested).decode_utf8() {
+            Ok(decoded) => decoded.into_owned(),
+            Err(_) => {
+                return self.serve_static_html(
+                    "<html><body><h1>400 Bad Request</h1><p>Invalid percent-encoding in path.</p></body></html>",
+                    "HTTP/1.1 400 Bad Request",
+                    keep_alive,
+                ).await;
+            }
+        };
+
+        // Construct the full path (relative to a 'public' directory) and make sure it
+        // can't escape the public root, even via an encoded `..%2f` sequence.
+        let public_root = Path::new("public");
+        let canonical_public = match public_root.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(_) => {
+                return self.serve_static_html(
+                    "<html><body><h1>500 Internal Server Error</h1><p>Could not resolve public root.</p></body></html>",
+                    "HTTP/1.1 500 Internal Server Error",
+                    keep_alive,
+                ).await;
+            }
+        };
+        let file_path = match resolve_file_path(&decoded, public_root, &canonical_public) {
+            Ok(path) => path,
+            Err(PathResolveError::Forbidden) => {
+                return self.serve_static_html(
+                    "<html><body><h1>403 Forbidden</h1><p>Access denied.</p></body></html>",
+                    "HTTP/1.1 403 Forbidden",
+                    keep_alive,
+                ).await;
+            }
+        };
+
+        // Check if it's a directory
+        if file_path.is_dir() {
+            return self.serve_directory_listing(&file_path, &canonical_public, keep_alive).await;
         }
-        Err(_) => {
-            // Malformed request
-            let response =
-                "HTTP/1.1 400 Bad Request\r\nContent-Length: 24\r\n\r\nMalformed HTTP request";
-            socket.write_all(response.as_bytes()).await?;
+
+        // Try to open file asynchronously
+        match File::open(&file_path).await {
+            Ok(mut file) => {
+                let extension = file_path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+                if matches!(extension.as_deref(), Some("md") | Some("markdown")) {
+                    let mut raw = String::new();
+                    return match file.read_to_string(&mut raw).await {
+                        Ok(_) => self.serve_static_html(&render_markdown(&raw), "HTTP/1.1 200 OK", keep_alive).await,
+                        Err(_) => {
+                            self.serve_static_html(
+                                "<html><body><h1>500 Internal Server Error</h1><p>Markdown file is not valid UTF-8.</p></body></html>",
+                                "HTTP/1.1 500 Internal Server Error",
+                                keep_alive,
+                            ).await
+                        }
+                    };
+                }
+
+                let len = file.metadata().await?.len();
+                let content_type = content_type_for(&file_path);
+
+                if let Some(range_value) = range_header {
+                    match parse_range_header(range_value, len) {
+                        Err(()) => {
+                            self.status_code = 416;
+                            let response = format!(
+                                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: {}\r\n\r\n",
+                                len,
+                                connection_header(keep_alive)
+                            );
+                            return self.write_all(response.as_bytes()).await;
+                        }
+                        Ok(Some((start, end))) => {
+                            let range_len = end - start + 1;
+                            file.seek(std::io::SeekFrom::Start(start)).await?;
+
+                            self.status_code = 206;
+                            let response = format!(
+                                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\nContent-Type: {}\r\nConnection: {}\r\n\r\n",
+                                start, end, len, range_len, content_type, connection_header(keep_alive)
+                            );
+                            self.write_all(response.as_bytes()).await?;
+                            self.stream_body(&mut file, range_len).await?;
+                            return Ok(());
+                        }
+                        Ok(None) => {
+                            // Header didn't match the grammar; fall through to a full response.
+                        }
+                    }
+                }
+
+                // Construct and send the response
+                self.status_code = 200;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: {}\r\nAccept-Ranges: bytes\r\nConnection: {}\r\n\r\n",
+                    len,
+                    content_type,
+                    connection_header(keep_alive)
+                );
+
+                self.write_all(response.as_bytes()).await?;
+                self.stream_body(&mut file, len).await?;
+            }
+            Err(_) => {
+                // File not found
+                self.serve_static_html(
+                    "<html><body><h1>404 Not Found</h1><p>The requested file could not be found.</p></body></html>",
+                    "HTTP/1.1 404 NOT FOUND",
+                    keep_alive,
+                ).await?
+            }
         }
+
+        Ok(())
     }
-    Ok(())
-}
 
-async fn handle_get_request(mut socket: TcpStream, path: &str) -> Result<(), Box<dyn Error>> {
-    // Create a simple router from different paths
-    return match path {
-        "/" => {
-            serve_static_html(
-                &mut socket,
-                "<html><body>
-                    <h1>Welcome to Tokio Async Server</h1>
-                    <p>This is a simple async HTTP server built with Tokio.</p>
-                    <ul>
-                        <li><a href='/'>Home</a></li>
-                        <li><a href='/about'>About</a></li>
-                        <li><a href='/files/index.html'>Static File Example</a></li>
-                        <li><a href='/files/'>Files Directory</a></li>
-                    </ul>
-                </body></html>",
-                "HTTP/1.1 200 OK",
-            ).await
-        }
-        "/about" => {
-            serve_static_html(
-                &mut socket,
-                "<html><body>
-                    <h1>About This Server</h1>
-                    <p>This is a demonstration of asynchronous programming in Rust using Tokio.</p>
-                    <p><a href='/'>Back to home</a></p>
-                </body></html>"
-                , "HTTP/1.1 200 OK",
-            ).await
+    async fn serve_directory_listing(&mut self, dir_path: &Path, public_root: &Path, keep_alive: bool) -> Result<(), Box<dyn Error>> {
+        // Read directory entries (this uses the standard library fs, not tokio's fs)
+        // because tokio doesn't have a direct equivalent to read_dir yet
+        let entries = match std::fs::read_dir(dir_path) {
+            Ok(entries) => entries,
+            Err(_) => {
+                return self.serve_static_html(
+                    "<html><body><h1>500 Internal Server Error</h1><p>Could not read directory.</p></body></html>",
+                    "HTTP/1.1 500 Internal Server Error",
+                    keep_alive,
+                ).await;
+            }
+        };
+
+        // Get the relative path for display. `dir_path` is canonicalized (absolute),
+        // so strip the canonicalized `public_root` rather than a hardcoded relative literal.
+        let rel_path = directory_rel_path(dir_path, public_root);
+
+        // Build HTML for directory listing
+        let mut html = format!("<html><body><h1>Directory: {}</h1><ul>", rel_path);
+
+        // Add parent directory link if not at the root
+        if rel_path != "/files/" {
+            html.push_str("<li><a href=\"../\">..</a> (Parent Directory)</li>");
         }
-        _ if path.starts_with("/files/") => {
-            // Handle file requests
-            serve_file(&mut socket, path).await
+
+        for entry in entries {
+            if let Ok(entry) = entry {
+                if let Ok(file_type) = entry.file_type() {
+                    let name = entry.file_name();
+                    let name_str = name.to_string_lossy();
+                    let href = percent_encoding::utf8_percent_encode(&name_str, PATH_SEGMENT);
+
+                    if file_type.is_dir() {
+                        html.push_str(&format!("<li><a href=\"{}/\">{}/</a></li>", href, name_str));
+                    } else {
+                        html.push_str(&format!("<li><a href=\"{}\">{}</a></li>", href, name_str));
+                    }
+                }
+            }
         }
-        _ => {
-            serve_static_html(
-                &mut socket,
-                "<html><body>
-                    <h1>404: Page not found</h1>
-                    <p>The requested resource could not be found.</p>
-                    <p><a href='/'>Back to home</a></p>
-                </body></html>",
-                "HTTP/1.1 404 NOT FOUND",
-            ).await
+
+        html.push_str("</ul></body></html>");
+
+        // Serve the HTML
+        self.serve_static_html(&html, "HTTP/1.1 200 OK", keep_alive).await
+    }
+
+    // Stream exactly `remaining` bytes from `file` in fixed-size chunks so a
+    // connection's memory use stays bounded regardless of how large the file is.
+    async fn stream_body(&mut self, file: &mut File, mut remaining: u64) -> Result<(), Box<dyn Error>> {
+        let mut buf = vec![0u8; 65536];
+        while remaining > 0 {
+            let to_read = std::cmp::min(buf.len() as u64, remaining) as usize;
+            let n = file.read(&mut buf[..to_read]).await?;
+            if n == 0 {
+                break;
+            }
+            self.write_all(&buf[..n]).await?;
+            remaining -= n as u64;
         }
-    };
+        Ok(())
+    }
 }
 
-// Helper function to serve static HTML content
-async fn serve_static_html(socket: &mut TcpStream, content: &str, status: &str) -> Result<(), Box<dyn Error>> {
-    let status_line = status.to_string();
-    let content_type = "text/html".to_string();
-
-    // Construct the full response
-    let response = format!(
-        "{}\r\nContent-Length: {}\r\nContent-Type: {}\r\nConnection: close\r\n\r\n{}",
-        status_line,
-        content.len(),
-        content_type,
-        content
-    );
-    // Write the response asynchronously
-    socket.write_all(response.as_bytes()).await?;
-
-    Ok(())
-}
+async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(socket: S, peer_addr: SocketAddr) -> Result<(), Box<dyn Error>> {
+    let mut handle = RequestHandle::new(socket, peer_addr);
+    let mut buffer = Vec::with_capacity(8192);
+    let mut read_buf = [0u8; 8192];
+
+    loop {
+        // Read (and re-read) until we have a complete request or hit EOF/timeout.
+        let request = loop {
+            match try_parse_request(&buffer) {
+                Err(()) => {
+                    handle.begin_request(String::new(), String::new());
+                    handle.status_code = 400;
+                    let body = "Malformed HTTP request";
+                    let response = format!(
+                        "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    handle.write_all(response.as_bytes()).await?;
+                    handle.log_access();
+                    return Ok(());
+                }
+                Ok(Some(request)) => break request,
+                Ok(None) => {
+                    if buffer.len() >= MAX_REQUEST_SIZE {
+                        handle.begin_request(String::new(), String::new());
+                        handle.status_code = 400;
+                        let body = "Incomplete request received";
+                        let response = format!(
+                            "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        handle.write_all(response.as_bytes()).await?;
+                        handle.log_access();
+                        return Ok(());
+                    }
+
+                    match timeout(IDLE_TIMEOUT, handle.socket.read(&mut read_buf)).await {
+                        Ok(Ok(0)) => return Ok(()), // Client closed the connection.
+                        Ok(Ok(n)) => buffer.extend_from_slice(&read_buf[..n]),
+                        Ok(Err(e)) => return Err(e.into()),
+                        Err(_) => return Ok(()), // Idle timeout.
+                    }
+                }
+            }
+        };
 
-// Helper function to serve files
-async fn serve_file(socket: &mut TcpStream, path: &str) -> Result<(), Box<dyn Error>> {
-    // Extract the file path from the URL
-    let file_path = path.trim_start_matches("/files/");
+        handle.begin_request(request.method.clone(), request.path.clone());
+        let keep_alive = should_keep_alive(&request);
 
-    // For security, ensure the path doesn't contain '..'
-    // to prevent directory traversal
-    if file_path.contains("..") {
-        serve_static_html(
-            socket,
-            "<html><body><h1>403 Forbidden</h1><p>Access denied.</p></body></html>",
-            "HTTP/1.1 403 Forbidden",
-        ).await?
-    }
-
-    // Construct the full path (relative to a 'public' directory)
-    let file_path = Path::new("public").join(file_path);
-    // Check if it's a directory
-    if file_path.is_dir() {
-        return serve_directory_listing(socket, &file_path).await;
-    }
-
-    // Try to open file asynchronously
-    match File::open(&file_path).await {
-        Ok(mut file) => {
-            // Read the file content
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents).await?;
-
-            // Determine content type based on file extension
-            let content_type = match file_path.extension().and_then(|e| e.to_str()) {
-                Some("html") => "text/html",
-                Some("css") => "text/css",
-                Some("js") => "application/javascript",
-                Some("jpg") | Some("jpeg") => "image/jpeg",
-                Some("png") => "image/png",
-                Some("gif") => "image/gif",
-                _ => "application/octet-stream",
-            };
-
-            // Construct and send the response
-            let response = format!(
-                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: {}\r\nConnection: close\r\n\r\n",
-                contents.len(),
-                content_type
-            );
-
-            socket.write_all(response.as_bytes()).await?;
-            socket.write_all(&contents).await?;
+        match request.method.as_str() {
+            "GET" => {
+                handle.handle_get_request(&request.path, request.range_header.as_deref(), keep_alive).await?
+            }
+            _ => {
+                // Respond with 405 Method Not Allowed
+                handle.status_code = 405;
+                handle.write_all(b"HTTP/1.1 405 Method Not Allowed\r\nConnection: close\r\n\r\n").await?;
+                handle.log_access();
+                buffer.drain(..request.consumed);
+                return Ok(());
+            }
         }
-        Err(_) => {
-            // File not found
-            serve_static_html(
-                socket,
-                "<html><body><h1>404 Not Found</h1><p>The requested file could not be found.</p></body></html>",
-                "HTTP/1.1 404 NOT FOUND",
-            ).await?
+
+        handle.log_access();
+        buffer.drain(..request.consumed);
+
+        if !keep_alive {
+            return Ok(());
         }
     }
+}
 
-    Ok(())
+// Derive the Content-Type for a path from its full extension via `mime_guess`,
+// appending a UTF-8 charset for textual formats so browsers render them correctly.
+fn content_type_for(path: &Path) -> String {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let essence = mime.essence_str();
+    if mime.type_() == mime_guess::mime::TEXT || essence == "application/javascript" || essence == "application/json" || essence == "image/svg+xml" {
+        format!("{}; charset=utf-8", essence)
+    } else {
+        essence.to_string()
+    }
 }
 
-async fn serve_directory_listing(socket: &mut TcpStream, dir_path: &Path) -> Result<(), Box<dyn Error>> {
-    // Read directory entries (this uses the standard library fs, not tokio's fs)
-    // because tokio doesn't have a direct equivalent to read_dir yet
-    let entries = match std::fs::read_dir(dir_path) {
-        Ok(entries) => entries,
-        Err(_) => {
-            return serve_static_html(socket,
-                                     "<html><body><h1>500 Internal Server Error</h1><p>Could not read directory.</p></body></html>",
-                                     "HTTP/1.1 500 Internal Server Error",
-            ).await;
-        }
-    };
-    
-    // Get the relative path for display
-    let rel_path = if dir_path == Path::new("public") {
+// Render Markdown to a minimal HTML document so `.md`/`.markdown` files can be
+// browsed directly instead of downloaded as raw text.
+fn render_markdown(input: &str) -> String {
+    let mut options = pulldown_cmark::Options::empty();
+    options.insert(pulldown_cmark::Options::ENABLE_TABLES);
+    options.insert(pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
+    options.insert(pulldown_cmark::Options::ENABLE_FOOTNOTES);
+    let parser = pulldown_cmark::Parser::new_ext(input, options);
+
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, parser);
+    format!("<html><body>{}</body></html>", body)
+}
+
+// Why `serve_file`'s traversal guard can reject a candidate path.
+#[derive(Debug, PartialEq, Eq)]
+enum PathResolveError {
+    Forbidden,
+}
+
+// Percent-decode `requested` is assumed to have already happened (the caller passes
+// the decoded relative path). Joins it onto `public_root` and canonicalizes the
+// result, rejecting anything that escapes `canonical_public` (including via an
+// encoded `..%2f` sequence). A candidate that doesn't exist yet canonicalizes to
+// `Err`, so it's returned as-is for the caller's 404 path to handle.
+fn resolve_file_path(decoded: &str, public_root: &Path, canonical_public: &Path) -> Result<PathBuf, PathResolveError> {
+    let candidate = public_root.join(decoded);
+    match candidate.canonicalize() {
+        Ok(canonical) if canonical.starts_with(canonical_public) => Ok(canonical),
+        Ok(_) => Err(PathResolveError::Forbidden),
+        Err(_) => Ok(candidate),
+    }
+}
+
+// Derive the `/files/...` breadcrumb for a directory listing from its canonicalized
+// path and the canonicalized public root.
+fn directory_rel_path(dir_path: &Path, public_root: &Path) -> String {
+    let rel = dir_path.strip_prefix(public_root).unwrap_or(Path::new(""));
+    if rel.as_os_str().is_empty() {
         "/files/".to_string()
     } else {
-        let rel = dir_path.strip_prefix("public").unwrap_or(Path::new(""));
         format!("/files/{}/", rel.display())
+    }
+}
+
+// Parse a `Range: bytes=start-end` header against the resource length, matching
+// the `^bytes=(\d*)-(\d*)$` grammar. Returns `Ok(None)` when the header doesn't match
+// the grammar or gives neither bound (the request should be served in full), `Ok(Some(..))`
+// with the clamped inclusive byte range, or `Err(())` when the range is unsatisfiable.
+fn parse_range_header(value: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let spec = match value.strip_prefix("bytes=") {
+        Some(spec) => spec,
+        None => return Ok(None),
     };
+    let (start_str, end_str) = match spec.split_once('-') {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    if !start_str.bytes().all(|b| b.is_ascii_digit())
+        || !end_str.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Ok(None);
+    }
+    if start_str.is_empty() && end_str.is_empty() {
+        return Ok(None);
+    }
+    if len == 0 {
+        return Err(());
+    }
 
-    // Build HTML for directory listing
-    let mut html = format!("<html><body><h1>Directory: {}</h1><ul>", rel_path);
-
-    // Add parent directory link if not at the root
-    if rel_path != "/files/" {
-        html.push_str("<li><a href=\"../\">..</a> (Parent Directory)</li>");
-    }
-    
-    for entry in entries {
-        if let Ok(entry) = entry {
-            if let Ok(file_type) = entry.file_type() {
-                let name = entry.file_name();
-                let name_str = name.to_string_lossy();
-                
-                if file_type.is_dir() {
-                    html.push_str(&format!("<li><a href=\"{}/\">{}/</a></li>", name_str, name_str));
-                } else {
-                    html.push_str(&format!("<li><a href=\"{}\">{}</a></li>", name_str, name_str));
-                }
-            }
+    let (start, end) = if start_str.is_empty() {
+        // Suffix range: the last N bytes of the resource.
+        let suffix_len: u64 = end_str.parse().map_err(|_| ())?;
+        if suffix_len == 0 {
+            return Err(());
         }
+        (len.saturating_sub(suffix_len), len - 1)
+    } else {
+        let start: u64 = start_str.parse().map_err(|_| ())?;
+        let end = if end_str.is_empty() {
+            len - 1
+        } else {
+            let end: u64 = end_str.parse().map_err(|_| ())?;
+            end.min(len - 1)
+        };
+        (start, end)
+    };
+
+    if start > end || start >= len {
+        return Err(());
     }
+    Ok(Some((start, end)))
+}
 
-    html.push_str("</ul></body></html>");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Serve the HTML
-    serve_static_html(socket, &html, "HTTP/1.1 200 OK").await
-}
+    #[test]
+    fn parse_range_header_unrecognized_serves_in_full() {
+        assert_eq!(parse_range_header("not-a-range", 1000), Ok(None));
+    }
+
+    #[test]
+    fn parse_range_header_both_empty_serves_in_full() {
+        assert_eq!(parse_range_header("bytes=-", 1000), Ok(None));
+    }
+
+    #[test]
+    fn parse_range_header_start_to_eof() {
+        assert_eq!(parse_range_header("bytes=500-", 1000), Ok(Some((500, 999))));
+    }
+
+    #[test]
+    fn parse_range_header_suffix() {
+        assert_eq!(parse_range_header("bytes=-500", 1000), Ok(Some((500, 999))));
+    }
+
+    #[test]
+    fn parse_range_header_suffix_larger_than_file_clamps_to_start() {
+        assert_eq!(parse_range_header("bytes=-5000", 1000), Ok(Some((0, 999))));
+    }
+
+    #[test]
+    fn parse_range_header_inclusive_span() {
+        assert_eq!(parse_range_header("bytes=0-499", 1000), Ok(Some((0, 499))));
+    }
 
+    #[test]
+    fn parse_range_header_end_clamps_to_len_minus_one() {
+        assert_eq!(parse_range_header("bytes=0-999999", 1000), Ok(Some((0, 999))));
+    }
+
+    #[test]
+    fn parse_range_header_start_beyond_len_is_unsatisfiable() {
+        assert_eq!(parse_range_header("bytes=1000-", 1000), Err(()));
+    }
+
+    #[test]
+    fn parse_range_header_start_after_end_is_unsatisfiable() {
+        assert_eq!(parse_range_header("bytes=500-100", 1000), Err(()));
+    }
+
+    #[test]
+    fn parse_range_header_zero_length_file_is_unsatisfiable() {
+        assert_eq!(parse_range_header("bytes=0-0", 0), Err(()));
+    }
+
+    struct ScratchRoot {
+        path: PathBuf,
+    }
+
+    impl ScratchRoot {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("async_http_server_test_{}_{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(path.join("public/sub")).unwrap();
+            std::fs::write(path.join("public/sub/file.txt"), b"hi").unwrap();
+            std::fs::write(path.join("public/my file.txt"), b"hi").unwrap();
+            std::fs::write(path.join("outside.txt"), b"secret").unwrap();
+            Self { path }
+        }
+
+        fn public_root(&self) -> PathBuf {
+            self.path.join("public")
+        }
+    }
+
+    impl Drop for ScratchRoot {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+
+    #[test]
+    fn resolve_file_path_allows_files_inside_root() {
+        let scratch = ScratchRoot::new("allows");
+        let public_root = scratch.public_root();
+        let canonical_public = public_root.canonicalize().unwrap();
+
+        let resolved = resolve_file_path("sub/file.txt", &public_root, &canonical_public).unwrap();
+        assert!(resolved.starts_with(&canonical_public));
+    }
+
+    #[test]
+    fn resolve_file_path_decodes_before_this_call() {
+        // The caller percent-decodes before calling; this exercises the decoded form directly.
+        let scratch = ScratchRoot::new("decoded");
+        let public_root = scratch.public_root();
+        let canonical_public = public_root.canonicalize().unwrap();
+
+        let resolved = resolve_file_path("my file.txt", &public_root, &canonical_public).unwrap();
+        assert!(resolved.ends_with("my file.txt"));
+    }
+
+    #[test]
+    fn resolve_file_path_missing_file_falls_through_for_404() {
+        let scratch = ScratchRoot::new("missing");
+        let public_root = scratch.public_root();
+        let canonical_public = public_root.canonicalize().unwrap();
+
+        let resolved = resolve_file_path("does-not-exist.txt", &public_root, &canonical_public).unwrap();
+        assert_eq!(resolved, public_root.join("does-not-exist.txt"));
+    }
+
+    #[test]
+    fn resolve_file_path_rejects_traversal_outside_root() {
+        let scratch = ScratchRoot::new("traversal");
+        let public_root = scratch.public_root();
+        let canonical_public = public_root.canonicalize().unwrap();
+
+        let err = resolve_file_path("../outside.txt", &public_root, &canonical_public).unwrap_err();
+        assert_eq!(err, PathResolveError::Forbidden);
+    }
+
+    #[test]
+    fn directory_rel_path_at_root() {
+        let scratch = ScratchRoot::new("rel-root");
+        let canonical_public = scratch.public_root().canonicalize().unwrap();
+
+        assert_eq!(directory_rel_path(&canonical_public, &canonical_public), "/files/");
+    }
+
+    #[test]
+    fn directory_rel_path_in_subdirectory() {
+        let scratch = ScratchRoot::new("rel-sub");
+        let canonical_public = scratch.public_root().canonicalize().unwrap();
+        let sub = canonical_public.join("sub");
+
+        assert_eq!(directory_rel_path(&sub, &canonical_public), "/files/sub/");
+    }
+}